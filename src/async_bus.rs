@@ -0,0 +1,269 @@
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::future::Future;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::sync::Notify;
+use tokio::time::sleep;
+
+use crate::{Flow, Subscription, DEFAULT_PRIORITY};
+
+type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// The async counterpart to `Handler`, for handlers that need to await I/O, timers, etc.
+pub trait AsyncHandler<E>: Send + Sync {
+    fn handle<'a>(&'a self, event: &'a mut E) -> BoxFuture<'a, Flow>;
+}
+
+/// One registered handler: `(slot, priority, the boxed `Box<dyn AsyncHandler<E>>` for whichever
+/// `E` this TypeId bucket is for)`; see `crate::HandlerEntry` for the sync-bus equivalent.
+type HandlerEntry = (u64, i32, Box<dyn Any + Send + Sync>);
+
+/// An event bus for handlers that do async work, mirroring `EventBus` but with `async fn
+/// dispatch` in place of `call_event`.
+pub struct AsyncEventBus {
+    handler_cells: HashMap<TypeId, Vec<HandlerEntry>>,
+    next_slot: u64,
+}
+
+impl AsyncEventBus {
+    pub fn new() -> Self {
+        AsyncEventBus {
+            handler_cells: HashMap::new(),
+            next_slot: 0,
+        }
+    }
+
+    pub fn register_fn<E, F, Fut>(&mut self, handler: F) -> Subscription<E>
+        where E: 'static,
+              F: Fn(&mut E) -> Fut + Send + Sync + 'static,
+              Fut: Future<Output = Flow> + Send + 'static {
+        self.register_fn_with_priority(DEFAULT_PRIORITY, handler)
+    }
+
+    pub fn register_fn_with_priority<E, F, Fut>(&mut self, priority: i32, handler: F) -> Subscription<E>
+        where E: 'static,
+              F: Fn(&mut E) -> Fut + Send + Sync + 'static,
+              Fut: Future<Output = Flow> + Send + 'static {
+        self.register_handler_with_priority(priority, AsyncFnHandler::from(handler))
+    }
+
+    pub fn register_handler<E, F>(&mut self, handler: F) -> Subscription<E>
+        where E: 'static,
+              F: AsyncHandler<E> + 'static {
+        self.register_handler_with_priority(DEFAULT_PRIORITY, handler)
+    }
+
+    pub fn register_handler_with_priority<E, F>(&mut self, priority: i32, handler: F) -> Subscription<E>
+        where E: 'static,
+              F: AsyncHandler<E> + 'static {
+        let type_id = TypeId::of::<E>();
+        let slot = self.next_slot;
+        self.next_slot += 1;
+
+        let b: Box<dyn AsyncHandler<E>> = Box::new(handler); // keeps the Any type consistent, same as EventBus
+        let vec = self.handler_cells.entry(type_id)
+            .or_default();
+        // Sorted by descending priority, ties broken by registration order; see EventBus for the
+        // same scheme.
+        let pos = vec.partition_point(|(_, p, _)| *p >= priority);
+        vec.insert(pos, (slot, priority, Box::new(b)));
+
+        Subscription::new(type_id, slot)
+    }
+
+    /// Registers a handler that coalesces rapid-fire events: only the latest event received
+    /// within `period` of the last one is ever delivered to `handler`. Useful for attaching
+    /// expensive reactions (recompute, refresh) to high-frequency events without flooding them.
+    ///
+    /// The latest event is held in a plain `Mutex<Option<E>>` that each dispatch just overwrites,
+    /// with a `Notify` to wake the background task -- so a burst of dispatches with no scheduling
+    /// opportunity in between always leaves the *latest* event as the one waiting to be picked up,
+    /// not whichever arrived first, and each dispatched event is cloned exactly once.
+    pub fn register_debounced<E, F>(&mut self, period: Duration, handler: F) -> Subscription<E>
+        where E: Clone + Send + 'static,
+              F: Fn(E) + Send + 'static {
+        let pending: Arc<Mutex<Option<E>>> = Arc::new(Mutex::new(None));
+        let notify = Arc::new(Notify::new());
+
+        let task_pending = pending.clone();
+        let task_notify = notify.clone();
+        tokio::spawn(async move {
+            loop {
+                task_notify.notified().await;
+                loop {
+                    tokio::select! {
+                        _ = task_notify.notified() => continue,
+                        _ = sleep(period) => break,
+                    }
+                }
+                if let Some(event) = task_pending.lock().unwrap().take() {
+                    handler(event);
+                }
+            }
+        });
+
+        self.register_fn(move |event: &mut E| {
+            *pending.lock().unwrap() = Some(event.clone());
+            notify.notify_one();
+            async { Flow::Continue }
+        })
+    }
+
+    /// Dispatches `event` to its registered handlers in priority order, awaiting each in turn and
+    /// stopping early if one returns `Flow::Stop`. Returns `true` if the event was stopped
+    /// (consumed) by a handler, `false` if every handler ran to completion. Like `EventBus::
+    /// call_event`, `event` is passed by `&mut` so handlers can mutate it in place.
+    pub async fn dispatch<E>(&self, event: &mut E) -> bool
+        where E: 'static {
+        let type_id = TypeId::of::<E>();
+        if let Some(vec) = self.handler_cells.get(&type_id) {
+            for (_, _, handler) in vec {
+                let handler = handler.downcast_ref::<Box<dyn AsyncHandler<E>>>().unwrap().as_ref();
+                if handler.handle(event).await == Flow::Stop {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    /// Removes the handler identified by `sub`. Returns `true` if it was still registered.
+    pub fn unregister<E>(&mut self, sub: Subscription<E>) -> bool
+        where E: 'static {
+        if let Some(vec) = self.handler_cells.get_mut(&sub.type_id()) {
+            if let Some(index) = vec.iter().position(|(slot, _, _)| *slot == sub.slot()) {
+                vec.remove(index);
+                return true;
+            }
+        }
+        false
+    }
+}
+
+impl Default for AsyncEventBus {
+    fn default() -> Self {
+        AsyncEventBus::new()
+    }
+}
+
+struct AsyncFnHandler<E, F, Fut>
+    where F: Fn(&mut E) -> Fut,
+          Fut: Future<Output = Flow> {
+    dyn_fn: F,
+    // `fn(&mut E) -> Fut` rather than bare `E` so this struct's auto-trait (Send/Sync) impls
+    // don't spuriously require `E: Send + Sync` itself.
+    event: PhantomData<fn(&mut E) -> Fut>,
+}
+
+impl<E, F, Fut> AsyncHandler<E> for AsyncFnHandler<E, F, Fut>
+    where F: Fn(&mut E) -> Fut + Send + Sync,
+          Fut: Future<Output = Flow> + Send {
+    fn handle<'a>(&'a self, event: &'a mut E) -> BoxFuture<'a, Flow> {
+        Box::pin((self.dyn_fn)(event))
+    }
+}
+
+impl<E, F, Fut> From<F> for AsyncFnHandler<E, F, Fut>
+    where F: Fn(&mut E) -> Fut,
+          Fut: Future<Output = Flow> {
+    fn from(dyn_fn: F) -> Self {
+        AsyncFnHandler { dyn_fn, event: PhantomData }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use crate::Flow;
+
+    use super::AsyncEventBus;
+
+    #[derive(Debug)]
+    struct SomeEvent {
+        some_data: u32,
+    }
+
+    #[tokio::test]
+    async fn dispatch_order_and_stop() {
+        let mut event_bus = AsyncEventBus::new();
+
+        event_bus.register_fn_with_priority(10, |e: &mut SomeEvent| {
+            e.some_data += 1;
+            async { Flow::Stop }
+        });
+        event_bus.register_fn(|e: &mut SomeEvent| {
+            e.some_data += 100; // should never run, the higher priority handler stopped it
+            async { Flow::Continue }
+        });
+
+        let mut some_event = SomeEvent { some_data: 0 };
+        let stopped = event_bus.dispatch(&mut some_event).await;
+
+        assert!(stopped);
+        assert_eq!(some_event.some_data, 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn debounce_coalesces_rapid_events() {
+        let received = Arc::new(Mutex::new(Vec::new()));
+
+        let mut event_bus = AsyncEventBus::new();
+        let received_in_handler = received.clone();
+        event_bus.register_debounced(Duration::from_millis(100), move |event: u32| {
+            received_in_handler.lock().unwrap().push(event);
+        });
+
+        // Three rapid-fire events inside the debounce window; only the last should survive. Each
+        // dispatch is followed by a yield so the background debounce task gets polled and picks
+        // up each event as it arrives, rather than only seeing the latest one on its next poll.
+        event_bus.dispatch(&mut 1u32).await;
+        tokio::task::yield_now().await;
+        tokio::time::advance(Duration::from_millis(10)).await;
+        event_bus.dispatch(&mut 2u32).await;
+        tokio::task::yield_now().await;
+        tokio::time::advance(Duration::from_millis(10)).await;
+        event_bus.dispatch(&mut 3u32).await;
+        tokio::task::yield_now().await;
+
+        assert!(received.lock().unwrap().is_empty(), "handler must not fire before the debounce period elapses");
+
+        // Let the timer elapse since the last (3rd) event, then give the background task a
+        // chance to run.
+        tokio::time::advance(Duration::from_millis(100)).await;
+        tokio::task::yield_now().await;
+
+        assert_eq!(*received.lock().unwrap(), vec![3]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn debounce_keeps_latest_value_in_a_tight_burst() {
+        let received = Arc::new(Mutex::new(Vec::new()));
+
+        let mut event_bus = AsyncEventBus::new();
+        let received_in_handler = received.clone();
+        event_bus.register_debounced(Duration::from_millis(100), move |event: u32| {
+            received_in_handler.lock().unwrap().push(event);
+        });
+
+        // Back-to-back dispatches with no yield in between: the background debounce task never
+        // gets a scheduling opportunity until after all three are queued, so only whichever event
+        // is latest when it finally looks may survive.
+        event_bus.dispatch(&mut 1u32).await;
+        event_bus.dispatch(&mut 2u32).await;
+        event_bus.dispatch(&mut 3u32).await;
+
+        // One yield lets the background task run for the first time and see the merged value;
+        // it has no opportunity to do so any earlier, since the events above never yielded.
+        tokio::task::yield_now().await;
+        tokio::time::advance(Duration::from_millis(100)).await;
+        tokio::task::yield_now().await;
+
+        assert_eq!(*received.lock().unwrap(), vec![3]);
+    }
+}
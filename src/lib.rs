@@ -2,45 +2,214 @@ use std::any::{Any, TypeId};
 use std::collections::HashMap;
 use std::marker::PhantomData;
 
+pub mod async_bus;
+
 pub trait Handler<E> {
-    fn handle(&self, event: &mut E);
+    fn handle(&self, event: &mut E) -> Flow;
+}
+
+/// Controls whether an event keeps propagating to the remaining handlers after one has run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flow {
+    /// Let the event continue on to the next handler.
+    Continue,
+    /// Stop the event here; no later (lower-priority) handlers will see it.
+    Stop,
 }
 
+/// Default priority used by `register_fn`/`register_handler`. Handlers registered with a
+/// higher priority run before handlers with a lower one.
+pub const DEFAULT_PRIORITY: i32 = 0;
+
+/// Links a dispatch "category" marker type to the single concrete event type its handlers
+/// operate on. `register_handler_with_priority_as`/`call_event_as!` key handler storage off
+/// `Category`'s `TypeId`, but the `Event` associated type fixes what they're downcast as, so the
+/// handlers they file under a category are all for that one event type (plain registrations are
+/// not checked against it; see `register_handler_with_priority_as`).
+///
+/// `call_event_as!` also requires every plain event type in its category list to implement this
+/// (with `Event = Self`) — there's no blanket impl, since that would collide with marker types
+/// like the ones above, so add `impl EventCategory for YourEvent { type Event = YourEvent; }`
+/// wherever you want to dispatch `YourEvent` alongside a category built on top of it.
+pub trait EventCategory: 'static {
+    type Event: 'static;
+}
+
+/// One registered handler: `(slot, priority, the boxed `Box<dyn Handler<E>>` for whichever `E`
+/// this TypeId bucket is for)`.
+type HandlerEntry = (u64, i32, Box<dyn Any>);
+
 pub struct EventBus {
-    handler_cells: HashMap<TypeId, Vec<Box<dyn Any>>>
+    handler_cells: HashMap<TypeId, Vec<HandlerEntry>>,
+    next_slot: u64,
+}
+
+/// A handle returned by `register_fn`/`register_handler` (and their priority variants) that can
+/// be passed to `EventBus::unregister` to remove that specific handler.
+///
+/// It is intentionally not `Copy`/`Clone`: each handler has exactly one handle, so a handle
+/// cannot accidentally unregister a different handler after being used once.
+pub struct Subscription<E> {
+    type_id: TypeId,
+    slot: u64,
+    _marker: PhantomData<E>,
+}
+
+impl<E> Subscription<E> {
+    pub(crate) fn new(type_id: TypeId, slot: u64) -> Self {
+        Subscription { type_id, slot, _marker: PhantomData }
+    }
+
+    pub(crate) fn type_id(&self) -> TypeId {
+        self.type_id
+    }
+
+    pub(crate) fn slot(&self) -> u64 {
+        self.slot
+    }
 }
 
 impl EventBus {
     pub fn new() -> Self {
         EventBus {
-            handler_cells: HashMap::new()
+            handler_cells: HashMap::new(),
+            next_slot: 0,
         }
     }
 
-    pub fn register_fn<E, F>(&mut self, handler: F)
+    pub fn register_fn<E, F>(&mut self, handler: F) -> Subscription<E>
+        where E: 'static,
+              F: Fn(&mut E) + 'static {
+        self.register_fn_with_priority(DEFAULT_PRIORITY, handler)
+    }
+
+    pub fn register_fn_with_priority<E, F>(&mut self, priority: i32, handler: F) -> Subscription<E>
         where E: 'static,
               F: Fn(&mut E) + 'static {
-        self.register_handler(FnHandler::from(handler));
+        self.register_handler_with_priority(priority, FnHandler::from(handler))
+    }
+
+    /// Like `register_fn`, but for closures that want to control propagation by returning a
+    /// `Flow` instead of implicitly continuing.
+    pub fn register_flow_fn<E, F>(&mut self, handler: F) -> Subscription<E>
+        where E: 'static,
+              F: Fn(&mut E) -> Flow + 'static {
+        self.register_flow_fn_with_priority(DEFAULT_PRIORITY, handler)
+    }
+
+    pub fn register_flow_fn_with_priority<E, F>(&mut self, priority: i32, handler: F) -> Subscription<E>
+        where E: 'static,
+              F: Fn(&mut E) -> Flow + 'static {
+        self.register_handler_with_priority(priority, FlowFnHandler::from(handler))
+    }
+
+    pub fn register_handler<E, F>(&mut self, handler: F) -> Subscription<E>
+        where E: 'static,
+              F: Handler<E> + 'static {
+        self.register_handler_with_priority(DEFAULT_PRIORITY, handler)
     }
 
-    pub fn register_handler<E, F>(&mut self, handler: F)
+    pub fn register_handler_with_priority<E, F>(&mut self, priority: i32, handler: F) -> Subscription<E>
         where E: 'static,
               F: Handler<E> + 'static {
+        let type_id = TypeId::of::<E>();
+        let slot = self.next_slot;
+        self.next_slot += 1;
+
         let b: Box<dyn Handler<E>>  = Box::new(handler); // This is required so that the Any type is consistent
-        self.handler_cells.entry(TypeId::of::<E>())
-            .or_insert_with(|| Vec::default())
-            .push(Box::new(b));
+        let vec = self.handler_cells.entry(type_id)
+            .or_default();
+        // Sorted by descending priority; ties broken by registration order, so find the first
+        // entry whose priority is lower than the new one and insert just before it.
+        let pos = vec.partition_point(|(_, p, _)| *p >= priority);
+        vec.insert(pos, (slot, priority, Box::new(b)));
+
+        Subscription::new(type_id, slot)
+    }
+
+    /// Like `register_handler` but files the handler under `Category`'s `TypeId` instead of its
+    /// event type's, so `call_event_as!` can merge it with handlers of other categories/types.
+    pub fn register_handler_as<Category, F>(&mut self, handler: F) -> Subscription<Category::Event>
+        where Category: EventCategory,
+              F: Handler<Category::Event> + 'static {
+        self.register_handler_with_priority_as::<Category, F>(DEFAULT_PRIORITY, handler)
+    }
+
+    /// Like `register_handler_with_priority`, but files the handler under `Category`'s `TypeId`
+    /// instead of its event type's. This is what lets `call_event_as!` merge handlers that were
+    /// registered for a shared base/category type with handlers registered for the concrete
+    /// event type.
+    ///
+    /// `Category: EventCategory` ties the storage key to exactly one concrete event type
+    /// (`Category::Event`), so every handler filed under a category through this method is for
+    /// that one event type; filing one for another type is a compile error. Plain
+    /// `register_handler`/`register_fn` key by the event's own `TypeId` without this check: a
+    /// handler registered for the category type itself as an event lands in the same bucket, and
+    /// dispatching that bucket then panics in `downcast_ref`.
+    pub fn register_handler_with_priority_as<Category, F>(&mut self, priority: i32, handler: F) -> Subscription<Category::Event>
+        where Category: EventCategory,
+              F: Handler<Category::Event> + 'static {
+        let type_id = TypeId::of::<Category>();
+        let slot = self.next_slot;
+        self.next_slot += 1;
+
+        let b: Box<dyn Handler<Category::Event>> = Box::new(handler); // This is required so that the Any type is consistent
+        let vec = self.handler_cells.entry(type_id)
+            .or_default();
+        // Sorted by descending priority; ties broken by registration order, so find the first
+        // entry whose priority is lower than the new one and insert just before it.
+        let pos = vec.partition_point(|(_, p, _)| *p >= priority);
+        vec.insert(pos, (slot, priority, Box::new(b)));
+
+        Subscription::new(type_id, slot)
     }
 
-    pub fn call_event<E>(&self, event: &mut E)
+    /// Removes the handler identified by `sub`. Returns `true` if it was still registered.
+    pub fn unregister<E>(&mut self, sub: Subscription<E>) -> bool
+        where E: 'static {
+        if let Some(vec) = self.handler_cells.get_mut(&sub.type_id) {
+            if let Some(index) = vec.iter().position(|(slot, _, _)| *slot == sub.slot) {
+                vec.remove(index);
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Dispatches `event` to its registered handlers in priority order, stopping early if a
+    /// handler returns `Flow::Stop`. Returns `true` if the event was stopped (consumed) by a
+    /// handler, `false` if every handler ran to completion.
+    pub fn call_event<E>(&self, event: &mut E) -> bool
         where E: 'static {
         let type_id = TypeId::of::<E>();
         if let Some(vec) = self.handler_cells.get(&type_id) {
-            for handler in vec {
-                let handler: &Box<dyn Handler<E>> = (*handler).downcast_ref().unwrap();
-                handler.handle(event);
+            for (_, _, handler) in vec {
+                let handler = handler.downcast_ref::<Box<dyn Handler<E>>>().unwrap().as_ref();
+                if handler.handle(event) == Flow::Stop {
+                    return true;
+                }
             }
         }
+        false
+    }
+
+    /// Low-level accessor used by `call_event_as!` to merge the handlers filed under `Category`
+    /// (via `register_handler_with_priority_as`) with those of other categories/types before
+    /// dispatching. Entries are `(slot, priority, handler)`, already sorted by descending
+    /// priority with ties in registration order, same as `call_event` relies on.
+    ///
+    /// Unlike a raw `TypeId` lookup, the return type is pinned to `Category::Event` by the
+    /// `EventCategory` bound, so merging two categories whose `Event` types differ is a type
+    /// mismatch at the `call_event_as!` call site, not a `downcast_ref` panic at dispatch time.
+    pub fn handlers_for_category_as<Category>(&self) -> impl Iterator<Item = (u64, i32, &dyn Handler<Category::Event>)>
+        where Category: EventCategory {
+        self.handler_cells.get(&TypeId::of::<Category>())
+            .into_iter()
+            .flatten()
+            .map(|(slot, priority, handler)| {
+                let handler = handler.downcast_ref::<Box<dyn Handler<Category::Event>>>().unwrap().as_ref();
+                (*slot, *priority, handler)
+            })
     }
 }
 
@@ -57,16 +226,84 @@ struct FnHandler<E, F: Fn(&mut E)> {
 }
 
 impl<E, F: Fn(&mut E)> Handler<E> for FnHandler<E, F> {
-    fn handle(&self, event: &mut E) {
+    fn handle(&self, event: &mut E) -> Flow {
         let dyn_fn = &self.dyn_fn;
-        dyn_fn(event)
+        dyn_fn(event);
+        Flow::Continue
     }
 }
 
 impl<F, E> From<F> for FnHandler<E, F>
     where F: Fn(&mut E) {
     fn from(dyn_fn: F) -> Self {
-        FnHandler { dyn_fn, event: PhantomData::default() }
+        FnHandler { dyn_fn, event: PhantomData }
+    }
+}
+
+/// A handler implementation for closures that want to control propagation themselves.
+struct FlowFnHandler<E, F: Fn(&mut E) -> Flow> {
+    dyn_fn: F,
+    event: PhantomData<E>,
+}
+
+impl<E, F: Fn(&mut E) -> Flow> Handler<E> for FlowFnHandler<E, F> {
+    fn handle(&self, event: &mut E) -> Flow {
+        let dyn_fn = &self.dyn_fn;
+        dyn_fn(event)
+    }
+}
+
+/// Dispatches `$event` against the merged, priority-ordered handlers of every `$category` in the
+/// list, instead of just `$event`'s own type. Pair with `register_handler_with_priority_as` (or
+/// plain `register_handler`, which files under the event's own type) to have one handler react to
+/// several categories an event logically belongs to, without calling `call_event` once per
+/// category. Returns `true` if a handler stopped the event (see `Flow::Stop`).
+///
+/// Every `$category` must implement `EventCategory` with the same `Event` type as `$event` — each
+/// one is fetched through `handlers_for_category_as::<$category>()`, and merging two whose `Event`
+/// types disagree is rejected by the compiler rather than panicking at dispatch time:
+///
+/// ```compile_fail
+/// use event_bus_sync_rs::{call_event_as, EventBus, EventCategory};
+///
+/// struct SomeEvent;
+/// struct OtherEvent;
+///
+/// struct CategoryA;
+/// impl EventCategory for CategoryA { type Event = SomeEvent; }
+/// struct CategoryB;
+/// impl EventCategory for CategoryB { type Event = OtherEvent; }
+///
+/// let bus = EventBus::new();
+/// let mut event = SomeEvent;
+/// call_event_as!(&bus, &mut event, [CategoryA, CategoryB]);
+/// ```
+#[macro_export]
+macro_rules! call_event_as {
+    ($bus:expr, $event:expr, [ $($category:ty),+ $(,)? ]) => {{
+        let mut merged: Vec<(u64, i32, &dyn $crate::Handler<_>)> = Vec::new();
+        $(
+            for (slot, priority, handler) in $bus.handlers_for_category_as::<$category>() {
+                merged.push((slot, priority, handler));
+            }
+        )+
+        merged.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+
+        let mut stopped = false;
+        for (_, _, handler) in merged {
+            if handler.handle($event) == $crate::Flow::Stop {
+                stopped = true;
+                break;
+            }
+        }
+        stopped
+    }};
+}
+
+impl<F, E> From<F> for FlowFnHandler<E, F>
+    where F: Fn(&mut E) -> Flow {
+    fn from(dyn_fn: F) -> Self {
+        FlowFnHandler { dyn_fn, event: PhantomData }
     }
 }
 
@@ -79,6 +316,13 @@ mod tests {
         some_data: u32,
     }
 
+    // Lets SomeEvent be listed directly alongside a marker category (see
+    // call_event_as_merges_categories below) while still proving at compile time that both sides
+    // of the merge operate on the same event type.
+    impl crate::EventCategory for SomeEvent {
+        type Event = SomeEvent;
+    }
+
     #[derive(Debug)]
     struct NonRegisteredEvent {
         some_data: u32,
@@ -109,6 +353,127 @@ mod tests {
         assert_eq!(some_event.some_data, 3);
     }
 
+    #[test]
+    fn priority_order() {
+        let mut event_bus = EventBus::new();
+
+        event_bus.register_fn(|e: &mut SomeEvent| {
+            e.some_data = e.some_data * 10 + 1; // priority 0, registered first
+        });
+        event_bus.register_fn_with_priority(10, |e: &mut SomeEvent| {
+            e.some_data = e.some_data * 10 + 2; // priority 10, should run first
+        });
+        event_bus.register_fn(|e: &mut SomeEvent| {
+            e.some_data = e.some_data * 10 + 3; // priority 0, registered after the first one
+        });
+        event_bus.register_fn_with_priority(-5, |e: &mut SomeEvent| {
+            e.some_data = e.some_data * 10 + 4; // priority -5, should run last
+        });
+
+        let mut some_event = SomeEvent {
+            some_data: 0,
+        };
+        event_bus.call_event(&mut some_event);
+
+        assert_eq!(some_event.some_data, 2134);
+    }
+
+    #[test]
+    fn stop_propagation() {
+        use crate::Flow;
+
+        let mut event_bus = EventBus::new();
+
+        event_bus.register_flow_fn_with_priority(10, |e: &mut SomeEvent| {
+            e.some_data += 1;
+            Flow::Stop
+        });
+        event_bus.register_fn(|e: &mut SomeEvent| {
+            e.some_data += 100; // should never run, the higher priority handler stopped it
+        });
+
+        let mut some_event = SomeEvent {
+            some_data: 0,
+        };
+        let consumed = event_bus.call_event(&mut some_event);
+
+        assert!(consumed);
+        assert_eq!(some_event.some_data, 1);
+    }
+
+    #[test]
+    fn unregister_handler() {
+        let mut event_bus = EventBus::new();
+
+        let sub = event_bus.register_fn(|e: &mut SomeEvent| {
+            e.some_data += 100; // should not run once unregistered
+        });
+        event_bus.register_fn(|e: &mut SomeEvent| {
+            e.some_data += 1;
+        });
+
+        assert!(event_bus.unregister(sub));
+
+        let mut some_event = SomeEvent {
+            some_data: 0,
+        };
+        event_bus.call_event(&mut some_event);
+
+        assert_eq!(some_event.some_data, 1);
+    }
+
+    #[test]
+    fn unregister_is_single_use() {
+        let mut event_bus = EventBus::new();
+
+        let sub = event_bus.register_fn(|e: &mut SomeEvent| {
+            e.some_data += 1;
+        });
+
+        assert!(event_bus.unregister(sub));
+        // the Subscription was consumed above, so there is nothing left to unregister; a second
+        // handler registered afterwards must not be affected by any stale slot reuse.
+        event_bus.register_fn(|e: &mut SomeEvent| {
+            e.some_data += 5;
+        });
+
+        let mut some_event = SomeEvent {
+            some_data: 0,
+        };
+        event_bus.call_event(&mut some_event);
+
+        assert_eq!(some_event.some_data, 5);
+    }
+
+    #[test]
+    fn call_event_as_merges_categories() {
+        struct BaseEvent;
+
+        impl crate::EventCategory for BaseEvent {
+            type Event = SomeEvent;
+        }
+
+        let mut event_bus = EventBus::new();
+
+        // Filed under the "BaseEvent" category, even though it still operates on SomeEvent.
+        event_bus.register_handler_with_priority_as::<BaseEvent, _>(10, super::FlowFnHandler::from(|e: &mut SomeEvent| {
+            e.some_data = e.some_data * 10 + 1;
+            crate::Flow::Continue
+        }));
+        // Filed under SomeEvent's own type, at a lower priority.
+        event_bus.register_fn(|e: &mut SomeEvent| {
+            e.some_data = e.some_data * 10 + 2;
+        });
+
+        let mut some_event = SomeEvent {
+            some_data: 0,
+        };
+        let stopped = call_event_as!(&event_bus, &mut some_event, [BaseEvent, SomeEvent]);
+
+        assert!(!stopped);
+        assert_eq!(some_event.some_data, 12);
+    }
+
     #[test]
     fn non_registered() {
         let event_bus = EventBus::new();